@@ -1,15 +1,23 @@
 //! `Loader`s are responsible for fetching chunks.
+use kismet_cache::Cache;
+use kismet_cache::CacheBuilder;
 use s3::bucket::Bucket;
 use s3::creds::Credentials;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::convert::TryInto;
 use std::io::ErrorKind;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::Weak;
 use std::time::Duration;
+use std::time::Instant;
 use tracing::instrument;
 use umash::Fingerprint;
 
@@ -39,9 +47,59 @@ const LOAD_RETRY_MULTIPLIER: f64 = 10.0;
 /// there is any external strong reference to them.
 const LRU_CACHE_SIZE: usize = 128;
 
+/// Keep up to this many recently-missing fingerprints in
+/// `MISSING_CHUNKS`, regardless of whether their entries have
+/// expired yet.
+const MISSING_CACHE_SIZE: usize = 4096;
+
 /// Load chunks in a dedicated thread pool of this size.
 const LOADER_POOL_SIZE: usize = 10;
 
+/// Name of the object that, when present in a bucket, marks it as
+/// using the packed bundle layout instead of today's one-object-
+/// per-chunk layout, and lists the name of every bundle object the
+/// bucket contains (one per line).
+const BUNDLE_MANIFEST_NAME: &str = "bundles.manifest";
+
+/// Suffix appended to a bundle object's name to get the name of its
+/// index object.
+const BUNDLE_INDEX_SUFFIX: &str = ".idx";
+
+/// Magic header for a bundle index blob ("VNBI", little-endian).
+const BUNDLE_INDEX_MAGIC: u32 = 0x49_42_4e_56;
+
+/// Version of the bundle index record format below.  Bump whenever
+/// the layout changes.
+const BUNDLE_INDEX_VERSION: u32 = 1;
+
+const BUNDLE_INDEX_HEADER_LEN: usize = 4 + 4 + 8;
+const BUNDLE_INDEX_RECORD_LEN: usize = 16 + 8 + 4;
+
+/// When coalescing chunk fetches within a packed bundle, merge two
+/// chunks into the same ranged GET if they are within this many
+/// bytes of one another.
+const BUNDLE_COALESCE_GAP: u64 = 64 * 1024;
+
+/// Shard the on-disk chunk cache into this many subdirectories, to
+/// keep any one directory from growing too large and to spread
+/// eviction work across `kismet_cache`'s shards.
+const DISK_CACHE_SHARDS: usize = 8;
+
+/// How long a fingerprint stays in `MISSING_CHUNKS` after every
+/// remote source reported it absent, before `fetch_chunk` will
+/// probe for it again.
+const NEGATIVE_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// How long a `RemoteSource::Loose`'s `known` listing is trusted
+/// before a lookup re-lists the bucket, so a chunk uploaded after the
+/// last scan doesn't stay permanently unreachable.
+const KNOWN_CHUNKS_TTL: Duration = Duration::from_secs(60);
+
+/// Delay before starting the hedged probe for the `i`th remote
+/// source (0-indexed).  A fast primary source (index 0, no delay)
+/// usually answers before a fallback's request is even issued.
+const HEDGE_STAGGER_DELAY: Duration = Duration::from_millis(10);
+
 #[derive(Eq, PartialEq, Debug)]
 // `#[non_exhaustive]` is too weak: it's a no-op within the crate.
 #[allow(clippy::manual_non_exhaustive)]
@@ -52,10 +110,118 @@ pub(crate) struct Chunk {
     _use_constructor: (),
 }
 
+/// A chunk's byte range within a packed bundle object.  The bundle's
+/// name is stored next to this entry, in `RemoteSource::Packed`'s
+/// `location` map.
+#[derive(Clone, Copy, Debug)]
+struct BundleIndexEntry {
+    offset: u64,
+    len: u32,
+}
+
+/// A bucket's most recently listed set of known chunk object names,
+/// and when that listing was taken; `refresh_known` re-lists once
+/// `listed_at` is older than `KNOWN_CHUNKS_TTL`.
+#[derive(Debug)]
+struct KnownChunks {
+    names: HashSet<String>,
+    listed_at: Instant,
+}
+
+/// A remote chunk source is either the original "loose" layout
+/// (one S3 object per chunk), or a "packed" bucket that bundles
+/// many chunks into a handful of larger objects, indexed by
+/// fingerprint.
+#[derive(Debug)]
+enum RemoteSource {
+    Loose {
+        bucket: Bucket,
+        /// Chunk object names known to exist in this bucket, from a
+        /// `ListObjectsV2` scan, refreshed every `KNOWN_CHUNKS_TTL`
+        /// so chunks uploaded after the last scan become reachable.
+        /// `None` when listing wasn't available, in which case every
+        /// lookup falls back to a direct GET.
+        known: Option<Mutex<KnownChunks>>,
+    },
+    Packed {
+        bucket: Bucket,
+        /// Maps each known fingerprint to the name of the bundle
+        /// that contains it and its byte range within that bundle.
+        location: HashMap<Fingerprint, (Arc<str>, BundleIndexEntry)>,
+    },
+}
+
 #[derive(Debug)]
 pub(crate) struct Loader {
     local_caches: Vec<PathBuf>,
-    remote_sources: Vec<Bucket>,
+    remote_sources: Vec<RemoteSource>,
+    /// A writable, size-bounded cache for chunks fetched from
+    /// `remote_sources`, shared by every `verneuil` process on this
+    /// host.  `None` when no disk cache directory was configured.
+    disk_cache: Option<Cache>,
+}
+
+/// A cheap, cloneable handle a caller can use to ask an in-flight
+/// `fetch_all_chunks` call to stop early, e.g. on process shutdown.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub(crate) fn new() -> CancellationToken {
+        Default::default()
+    }
+
+    /// Asks every holder of this token to stop as soon as possible.
+    pub(crate) fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A shared sink a caller can poll for how far a `fetch_all_chunks`
+/// call has gotten, to surface a completion percentage instead of
+/// appearing hung on a large restore.
+#[derive(Debug, Default)]
+pub(crate) struct FetchProgress {
+    fetched: AtomicUsize,
+    total: AtomicUsize,
+}
+
+impl FetchProgress {
+    pub(crate) fn new() -> FetchProgress {
+        Default::default()
+    }
+
+    fn set_total(&self, total: usize) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    fn increment(&self) {
+        self.fetched.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns `(fetch attempts completed so far, total distinct
+    /// chunks requested)`.  A completed attempt counts whether or not
+    /// the chunk was found.
+    pub(crate) fn progress(&self) -> (usize, usize) {
+        (
+            self.fetched.load(Ordering::Relaxed),
+            self.total.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// The result of a (possibly cancelled) `fetch_all_chunks` call.
+#[derive(Debug)]
+pub(crate) struct FetchAllOutcome {
+    /// Every chunk we managed to fetch before stopping.
+    pub(crate) chunks: HashMap<Fingerprint, Arc<Chunk>>,
+    /// Set when `cancel` had fired by the time the call returned,
+    /// whether or not any chunk was actually skipped.
+    pub(crate) aborted: bool,
 }
 
 lazy_static::lazy_static! {
@@ -69,6 +235,17 @@ lazy_static::lazy_static! {
     static ref LIVE_CHUNKS: Mutex<HashMap<Fingerprint, Weak<Chunk>>> = Default::default();
 }
 
+lazy_static::lazy_static! {
+    // Tracks fingerprints recently found absent from every remote
+    // source, so concurrent `fetch_chunk` calls don't all pay for
+    // the same round trip to rediscover a miss. Bounded like
+    // `CACHED_CHUNKS`, since entries are only ever removed by
+    // `clear_missing` on a later hit and would otherwise grow
+    // without limit.
+    static ref MISSING_CHUNKS: Mutex<lru::LruCache<Fingerprint, Instant>> =
+        Mutex::new(lru::LruCache::new(MISSING_CACHE_SIZE));
+}
+
 lazy_static::lazy_static! {
     // This bounded LRU cache maintains strong references to hot
     // chunks, to avoid repeatedly dropping and re-fetching them.
@@ -139,11 +316,19 @@ impl Drop for Chunk {
 
 impl Loader {
     /// Creates a fresh `Loader` that looks for hits first in `local`,
-    /// and then in `remote`.  The `Loader` always check the sources
-    /// in order: low-to-high index in `local`, and then similar in
-    /// `remote`.
+    /// then in an on-disk cache rooted at `disk_cache_dir` (if any,
+    /// bounded to `disk_cache_size` bytes), and finally in `remote`.
+    /// The `Loader` always checks `local` in order, low-to-high index.
+    /// Sources in `remote` are probed concurrently, with each start
+    /// staggered by its index; only under `test_vfs` are they checked
+    /// one after the other in index order.
     #[instrument]
-    pub(crate) fn new(local: Vec<PathBuf>, remote: &[ReplicationTarget]) -> Result<Loader> {
+    pub(crate) fn new(
+        local: Vec<PathBuf>,
+        remote: &[ReplicationTarget],
+        disk_cache_dir: Option<PathBuf>,
+        disk_cache_size: u64,
+    ) -> Result<Loader> {
         let mut remote_sources = Vec::new();
 
         if !remote.is_empty() {
@@ -155,24 +340,59 @@ impl Loader {
             }
         }
 
+        let disk_cache = disk_cache_dir
+            .map(|dir| {
+                CacheBuilder::new()
+                    .shards(DISK_CACHE_SHARDS)
+                    .total_size(disk_cache_size)
+                    .build(dir)
+            })
+            .transpose()
+            .map_err(|e| chain_error!(e, "failed to open on-disk chunk cache"))?;
+
         Ok(Loader {
             local_caches: local,
             remote_sources,
+            disk_cache,
         })
     }
 
     /// Attempts to fetch the chunk for each fingerprint in `fprints`.
     ///
-    /// Return `Err` if any fetch fails.  Even on success, the return
-    /// value may be missing entries.
+    /// `cancel` lets the caller abort early, e.g. on process
+    /// shutdown: the parallel fetch checks it before starting each
+    /// chunk and between retry backoffs, and stops as soon as it
+    /// fires.  `progress` is updated as chunks are fetched, so the
+    /// caller can poll `progress.progress()` for a completion
+    /// percentage instead of waiting on a call that may run for a
+    /// long time on a large restore.
+    ///
+    /// Return `Err` if any fetch fails.  Even on success (including
+    /// when cancelled), the return value may be missing entries.
     pub(crate) fn fetch_all_chunks(
         &self,
         fprints: &[Fingerprint],
-    ) -> Result<HashMap<Fingerprint, Arc<Chunk>>> {
+        cancel: &CancellationToken,
+        progress: &FetchProgress,
+    ) -> Result<FetchAllOutcome> {
         use rand::prelude::SliceRandom;
 
         // Deduplicate fprints before fetching them.
         let targets: HashSet<Fingerprint> = fprints.iter().cloned().collect();
+        progress.set_total(targets.len());
+
+        // Opportunistically batch fingerprints that live in the
+        // same packed bundle into coalesced ranged GETs before
+        // falling back to the regular per-chunk path below.
+        // Hold strong references to the prefetched chunks until the
+        // loop below is done, so they can't be evicted from
+        // `CACHED_CHUNKS` and refetched one by one.
+        let _prefetched = prefetch_packed_bundles(
+            &self.remote_sources,
+            &targets,
+            self.disk_cache.as_ref(),
+            cancel,
+        )?;
 
         // Randomize the load order to avoid accidental hotspotting
         // when threads or processes load similar sets of chunks.
@@ -184,21 +404,41 @@ impl Loader {
 
             shuffled_targets
                 .into_par_iter()
-                .map(|fprint| self.fetch_chunk(fprint))
+                .map(|fprint| {
+                    if cancel.is_cancelled() {
+                        return Ok(None);
+                    }
+
+                    let result = self.fetch_chunk(fprint, cancel);
+                    progress.increment();
+                    result
+                })
                 .collect::<Result<_>>()
         })?;
 
-        Ok(chunks
-            .into_iter()
-            .filter_map(|chunk_or| chunk_or.map(|chunk| (chunk.fprint, chunk)))
-            .collect())
+        Ok(FetchAllOutcome {
+            chunks: chunks
+                .into_iter()
+                .filter_map(|chunk_or| chunk_or.map(|chunk| (chunk.fprint, chunk)))
+                .collect(),
+            aborted: cancel.is_cancelled(),
+        })
     }
 
     /// Attempts to return the bytes for chunk `fprint`.
     ///
-    /// Returns Ok(None) if nothing was found.
+    /// Checks `cancel` before making any network call, and passes it
+    /// down so a retry loop can bail out between backoffs instead of
+    /// sleeping through it.
+    ///
+    /// Returns Ok(None) if nothing was found, including when
+    /// `cancel` fired before a remote source could be reached.
     #[instrument(level = "debug")]
-    pub(crate) fn fetch_chunk(&self, fprint: Fingerprint) -> Result<Option<Arc<Chunk>>> {
+    pub(crate) fn fetch_chunk(
+        &self,
+        fprint: Fingerprint,
+        cancel: &CancellationToken,
+    ) -> Result<Option<Arc<Chunk>>> {
         if fprint == ZERO_FILLED_CHUNK.0 {
             return Ok(Some(ZERO_FILLED_CHUNK.1.clone()));
         }
@@ -235,11 +475,78 @@ impl Loader {
             }
         }
 
+        if let Some(cache) = &self.disk_cache {
+            if let Some(cached) = cache.get(&name) {
+                update_contents(cached)?;
+                #[cfg(not(feature = "test_vfs"))]
+                return Ok(contents);
+            }
+        }
+
+        #[cfg(not(feature = "test_vfs"))]
+        if is_recently_missing(fprint) {
+            return Ok(contents);
+        }
+
+        // Under test_vfs, probe every source in order instead of
+        // hedging, so we can assert that they all agree on a
+        // chunk's contents; hedging deliberately leaves stragglers
+        // unobserved.
+        #[cfg(feature = "test_vfs")]
         for source in &self.remote_sources {
-            if let Some(remote) = load_from_source(&source, &name)? {
+            if cancel.is_cancelled() {
+                break;
+            }
+
+            let fetched = match source {
+                RemoteSource::Loose { bucket, known } => {
+                    let skip = matches!(known, Some(known) if !is_known(bucket, known, &name));
+                    if skip {
+                        None
+                    } else {
+                        load_from_source(bucket, &name, cancel)?
+                    }
+                }
+                RemoteSource::Packed { bucket, location } => match location.get(&fprint) {
+                    Some((bundle_name, entry)) => load_range_from_source(
+                        bucket,
+                        bundle_name,
+                        entry.offset,
+                        entry.len,
+                        cancel,
+                    )?,
+                    None => None,
+                },
+            };
+
+            if let Some(remote) = fetched {
+                clear_missing(fprint);
+
+                if let Some(cache) = &self.disk_cache {
+                    if let Err(e) = cache.put(&name, &remote) {
+                        tracing::info!(?e, %name, "failed to populate on-disk chunk cache");
+                    }
+                }
+
+                update_contents(remote)?;
+            }
+        }
+
+        #[cfg(not(feature = "test_vfs"))]
+        if !cancel.is_cancelled() {
+            if let Some(remote) = hedge_remote_fetch(&self.remote_sources, fprint, &name, cancel)? {
+                clear_missing(fprint);
+
+                if let Some(cache) = &self.disk_cache {
+                    if let Err(e) = cache.put(&name, &remote) {
+                        tracing::info!(?e, %name, "failed to populate on-disk chunk cache");
+                    }
+                }
+
                 update_contents(remote)?;
-                #[cfg(not(feature = "test_vfs"))]
                 return Ok(contents);
+            } else if !self.remote_sources.is_empty() && !cancel.is_cancelled() {
+                mark_missing(fprint);
             }
         }
 
@@ -247,6 +554,220 @@ impl Loader {
     }
 }
 
+/// Merges sorted `(fingerprint, entry)` pairs into runs that are
+/// within `BUNDLE_COALESCE_GAP` bytes of one another, each
+/// described as `(range_start, range_end, members)`.  Entries must
+/// already be sorted by `entry.offset`.
+fn coalesce_bundle_ranges(
+    entries: &[(Fingerprint, BundleIndexEntry)],
+) -> Vec<(u64, u64, Vec<(Fingerprint, BundleIndexEntry)>)> {
+    let mut runs: Vec<(u64, u64, Vec<(Fingerprint, BundleIndexEntry)>)> = Vec::new();
+
+    for &(fprint, entry) in entries {
+        let start = entry.offset;
+        let end = entry.offset + entry.len as u64;
+
+        match runs.last_mut() {
+            Some((_, run_end, members)) if start <= *run_end + BUNDLE_COALESCE_GAP => {
+                *run_end = (*run_end).max(end);
+                members.push((fprint, entry));
+            }
+            _ => runs.push((start, end, vec![(fprint, entry)])),
+        }
+    }
+
+    runs
+}
+
+/// A remote source's plan for fetching one chunk, resolved without
+/// any I/O so `hedge_remote_fetch` knows up front which sources are
+/// worth racing.
+enum HedgeProbe {
+    Loose(Bucket),
+    Packed(Bucket, Arc<str>, BundleIndexEntry),
+}
+
+/// Probes every remote source for `fprint`/`name` concurrently,
+/// staggering source `i`'s start by `i * HEDGE_STAGGER_DELAY`, and
+/// returns the first successful payload.  A 404 from one source
+/// doesn't count as a miss until every source has reported one.
+/// Stragglers still running once a winner (or a definitive all-miss)
+/// is found are left to finish in the background; their results are
+/// dropped.
+///
+/// These probes run on plain OS threads rather than `LOADER_POOL`:
+/// `fetch_chunk` (and thus this function) is itself invoked from
+/// `LOADER_POOL` worker threads via `fetch_all_chunks`'s
+/// `into_par_iter()`, and blocking a worker's `rx.recv()` on
+/// sub-tasks queued onto that same bounded pool can deadlock it once
+/// enough outer chunks are in flight to exhaust the pool. The probes
+/// are short I/O-bound HTTP calls, not CPU work, so they don't belong
+/// on the rayon pool anyway.
+fn hedge_remote_fetch(
+    remote_sources: &[RemoteSource],
+    fprint: Fingerprint,
+    name: &str,
+    cancel: &CancellationToken,
+) -> Result<Option<Vec<u8>>> {
+    let mut expected = 0usize;
+    let done = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mpsc::channel();
+
+    for (i, source) in remote_sources.iter().enumerate() {
+        let probe = match source {
+            RemoteSource::Loose { bucket, known } => {
+                let skip = matches!(known, Some(known) if !is_known(bucket, known, name));
+                if skip {
+                    None
+                } else {
+                    Some(HedgeProbe::Loose(bucket.clone()))
+                }
+            }
+            RemoteSource::Packed { bucket, location } => {
+                location.get(&fprint).map(|(bundle_name, entry)| {
+                    HedgeProbe::Packed(bucket.clone(), bundle_name.clone(), *entry)
+                })
+            }
+        };
+
+        let probe = match probe {
+            Some(probe) => probe,
+            None => continue,
+        };
+
+        expected += 1;
+        let tx = tx.clone();
+        let done = done.clone();
+        let cancel = cancel.clone();
+        let name = name.to_string();
+
+        std::thread::spawn(move || {
+            if i > 0 {
+                std::thread::sleep(HEDGE_STAGGER_DELAY * i as u32);
+            }
+
+            if done.load(Ordering::Relaxed) || cancel.is_cancelled() {
+                let _ = tx.send(Ok(None));
+                return;
+            }
+
+            let result = match probe {
+                HedgeProbe::Loose(bucket) => load_from_source(&bucket, &name, &cancel),
+                HedgeProbe::Packed(bucket, bundle_name, entry) => {
+                    load_range_from_source(&bucket, &bundle_name, entry.offset, entry.len, &cancel)
+                }
+            };
+
+            if matches!(result, Ok(Some(_))) {
+                done.store(true, Ordering::Relaxed);
+            }
+
+            // If the receiver is gone, a winner already returned:
+            // our result is simply dropped.
+            let _ = tx.send(result);
+        });
+    }
+
+    drop(tx);
+
+    let mut last_err = None;
+    for _ in 0..expected {
+        match rx.recv() {
+            Ok(Ok(Some(payload))) => return Ok(Some(payload)),
+            Ok(Ok(None)) => {}
+            Ok(Err(e)) => last_err = Some(e),
+            Err(_) => break,
+        }
+    }
+
+    match last_err {
+        Some(e) => Err(e),
+        None => Ok(None),
+    }
+}
+
+/// For every packed remote source, groups the fingerprints in
+/// `targets` that aren't already cached by bundle, and issues one
+/// coalesced ranged GET per run of nearby chunks, populating the
+/// chunk cache and returning the prefetched chunks, which the caller
+/// keeps alive while it fetches the rest.  This lets `fetch_all_chunks` turn a restore that
+/// touches thousands of chunks packed into a few bundles into a
+/// handful of requests instead of one GET per chunk.
+fn prefetch_packed_bundles(
+    remote_sources: &[RemoteSource],
+    targets: &HashSet<Fingerprint>,
+    disk_cache: Option<&Cache>,
+    cancel: &CancellationToken,
+) -> Result<Vec<Arc<Chunk>>> {
+    let mut prefetched = Vec::new();
+
+    for source in remote_sources {
+        if cancel.is_cancelled() {
+            return Ok(prefetched);
+        }
+
+        let (bucket, location) = match source {
+            RemoteSource::Packed { bucket, location } => (bucket, location),
+            RemoteSource::Loose { .. } => continue,
+        };
+
+        let mut by_bundle: HashMap<Arc<str>, Vec<(Fingerprint, BundleIndexEntry)>> = HashMap::new();
+        for fprint in targets {
+            if fetch_from_cache(*fprint).is_some() {
+                continue;
+            }
+
+            if let Some((bundle_name, entry)) = location.get(fprint) {
+                by_bundle
+                    .entry(bundle_name.clone())
+                    .or_default()
+                    .push((*fprint, *entry));
+            }
+        }
+
+        for (bundle_name, mut entries) in by_bundle {
+            entries.sort_by_key(|(_, entry)| entry.offset);
+
+            for (start, end, members) in coalesce_bundle_ranges(&entries) {
+                if cancel.is_cancelled() {
+                    return Ok(prefetched);
+                }
+
+                let payload = match load_range_from_source(
+                    bucket,
+                    &bundle_name,
+                    start,
+                    (end - start) as u32,
+                    cancel,
+                )? {
+                    Some(payload) => payload,
+                    None => continue,
+                };
+
+                for (fprint, entry) in members {
+                    let begin = (entry.offset - start) as usize;
+                    let slice = &payload[begin..begin + entry.len as usize];
+
+                    if let Some(cache) = disk_cache {
+                        let name = crate::replication_buffer::fingerprint_chunk_name(&fprint);
+                        if let Err(e) = cache.put(&name, slice) {
+                            tracing::info!(?e, %name, "failed to populate on-disk chunk cache");
+                        }
+                    }
+
+                    if let Ok(chunk) = Chunk::new(fprint, slice.to_vec()) {
+                        let chunk = Arc::new(chunk);
+                        update_cache(chunk.clone());
+                        prefetched.push(chunk);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(prefetched)
+}
+
 fn touch_cached_chunk(chunk: Arc<Chunk>) {
     let key = chunk.fprint;
 
@@ -266,6 +787,27 @@ fn update_cache(chunk: Arc<Chunk>) {
     touch_cached_chunk(chunk);
 }
 
+/// Returns `true` if every remote source recently reported `fprint`
+/// absent, and that finding hasn't yet expired.
+fn is_recently_missing(fprint: Fingerprint) -> bool {
+    let mut map = MISSING_CHUNKS.lock().expect("mutex should be valid");
+    matches!(map.get(&fprint), Some(at) if at.elapsed() < NEGATIVE_CACHE_TTL)
+}
+
+/// Records that `fprint` was just found absent from every remote
+/// source.
+fn mark_missing(fprint: Fingerprint) {
+    let mut map = MISSING_CHUNKS.lock().expect("mutex should be valid");
+    map.put(fprint, Instant::now());
+}
+
+/// Forgets any prior "missing" finding for `fprint`, now that it was
+/// found.
+fn clear_missing(fprint: Fingerprint) {
+    let mut map = MISSING_CHUNKS.lock().expect("mutex should be valid");
+    map.pop(&fprint);
+}
+
 /// Returns a cached chunk for `key`, if we have one.
 fn fetch_from_cache(key: Fingerprint) -> Option<Arc<Chunk>> {
     let upgraded = {
@@ -279,7 +821,7 @@ fn fetch_from_cache(key: Fingerprint) -> Option<Arc<Chunk>> {
 }
 
 #[instrument(level = "debug")]
-fn create_chunk_source(source: &ReplicationTarget, creds: &Credentials) -> Result<Bucket> {
+fn create_chunk_source(source: &ReplicationTarget, creds: &Credentials) -> Result<RemoteSource> {
     use ReplicationTarget::*;
 
     match source {
@@ -305,9 +847,189 @@ fn create_chunk_source(source: &ReplicationTarget, creds: &Credentials) -> Resul
                 bucket.set_path_style();
             }
 
-            Ok(bucket)
+            match bucket.get_object(BUNDLE_MANIFEST_NAME) {
+                Ok((manifest, 200)) => {
+                    let location = load_bundle_locations(&bucket, &manifest)?;
+                    Ok(RemoteSource::Packed { bucket, location })
+                }
+                Ok((_, 404)) => {
+                    let known = list_known_chunk_names(&bucket).map(|names| {
+                        Mutex::new(KnownChunks {
+                            names,
+                            listed_at: Instant::now(),
+                        })
+                    });
+                    Ok(RemoteSource::Loose { bucket, known })
+                }
+                Ok((body, code)) => Err(chain_error!(
+                    (body, code),
+                    "failed to fetch bundle manifest",
+                    %bucket.name
+                )),
+                Err(e) => Err(chain_error!(e, "failed to fetch bundle manifest", %bucket.name)),
+            }
+        }
+    }
+}
+
+/// Enumerates every chunk object in `bucket` with a paginated
+/// `ListObjectsV2` scan, so `fetch_chunk` can skip a GET (and its
+/// 404 round trip) for anything not listed.
+///
+/// Returns `None`, meaning "fall back to a direct GET for every
+/// lookup", when listing isn't available (e.g. the caller's
+/// credentials aren't allowed to list the bucket).
+#[instrument(level = "debug")]
+fn list_known_chunk_names(bucket: &Bucket) -> Option<HashSet<String>> {
+    match bucket.list(String::new(), None) {
+        Ok(pages) => Some(
+            pages
+                .into_iter()
+                .flat_map(|page| page.contents)
+                .map(|object| object.key)
+                .collect(),
+        ),
+        Err(e) => {
+            tracing::info!(
+                ?e,
+                %bucket.name,
+                "bucket listing unavailable, falling back to direct GETs"
+            );
+            None
+        }
+    }
+}
+
+/// Re-lists `bucket` into `known` if the current listing is older
+/// than `KNOWN_CHUNKS_TTL`, so chunks uploaded since the last scan
+/// become reachable again.  Leaves the stale listing in place (but
+/// still bumps `listed_at`, to avoid hammering an unreachable bucket
+/// on every lookup) if the re-list itself fails.
+fn refresh_known(bucket: &Bucket, known: &Mutex<KnownChunks>) {
+    let mut guard = known.lock().expect("mutex should be valid");
+    if guard.listed_at.elapsed() < KNOWN_CHUNKS_TTL {
+        return;
+    }
+
+    match list_known_chunk_names(bucket) {
+        Some(names) => {
+            *guard = KnownChunks {
+                names,
+                listed_at: Instant::now(),
+            }
+        }
+        None => guard.listed_at = Instant::now(),
+    }
+}
+
+/// Returns whether `name` is known to exist in `bucket`, refreshing
+/// the listing first if it's gone stale.
+fn is_known(bucket: &Bucket, known: &Mutex<KnownChunks>, name: &str) -> bool {
+    refresh_known(bucket, known);
+    known
+        .lock()
+        .expect("mutex should be valid")
+        .names
+        .contains(name)
+}
+
+/// Fetches and parses the index object for every bundle named in
+/// `manifest` (one bundle name per line), returning a map from
+/// fingerprint to the bundle that holds it and its byte range
+/// within that bundle.
+fn load_bundle_locations(
+    bucket: &Bucket,
+    manifest: &[u8],
+) -> Result<HashMap<Fingerprint, (Arc<str>, BundleIndexEntry)>> {
+    let mut location = HashMap::new();
+
+    let manifest = std::str::from_utf8(manifest)
+        .map_err(|e| chain_error!(e, "bundle manifest is not valid UTF-8"))?;
+
+    for bundle_name in manifest.lines().map(str::trim).filter(|s| !s.is_empty()) {
+        let index_name = format!("{}{}", bundle_name, BUNDLE_INDEX_SUFFIX);
+        let (payload, code) = bucket
+            .get_object(&index_name)
+            .map_err(|e| chain_error!(e, "failed to fetch bundle index", %index_name))?;
+
+        if code != 200 {
+            return Err(fresh_error!("missing bundle index", %index_name, code));
+        }
+
+        let bundle_name: Arc<str> = Arc::from(bundle_name);
+        for (fprint, entry) in parse_bundle_index(&payload)? {
+            location.insert(fprint, (bundle_name.clone(), entry));
+        }
+    }
+
+    Ok(location)
+}
+
+/// Parses a bundle index blob into sorted `(fingerprint, entry)`
+/// pairs.
+///
+/// The format is a fixed-size header (`magic: u32`, `version: u32`,
+/// `count: u64`, all little-endian) followed by `count` sorted
+/// records of `(fprint: [u8; 16], offset: u64, len: u32)`, each
+/// little-endian, so a reader can binary-search the index by
+/// fingerprint.  Readers must tolerate trailing padding after the
+/// last record.
+fn parse_bundle_index(bytes: &[u8]) -> Result<Vec<(Fingerprint, BundleIndexEntry)>> {
+    if bytes.len() < BUNDLE_INDEX_HEADER_LEN {
+        return Err(fresh_error!("bundle index truncated", len = bytes.len()));
+    }
+
+    let magic = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
+    let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+    let count = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+
+    if magic != BUNDLE_INDEX_MAGIC {
+        return Err(fresh_error!("bad bundle index magic", magic));
+    }
+
+    if version != BUNDLE_INDEX_VERSION {
+        return Err(fresh_error!("unsupported bundle index version", version));
+    }
+
+    // Compute how many records `bytes` can actually hold instead of
+    // multiplying `count` out: `count` comes straight off a fetched
+    // index object, and a corrupt or hostile one large enough to
+    // overflow the multiplication could otherwise wrap the product
+    // down to a tiny value that passes this check and then panics by
+    // indexing past the end of `bytes` below.
+    let available = bytes.len().saturating_sub(BUNDLE_INDEX_HEADER_LEN) / BUNDLE_INDEX_RECORD_LEN;
+    if (available as u64) < count {
+        return Err(fresh_error!(
+            "bundle index truncated",
+            len = bytes.len(),
+            count
+        ));
+    }
+
+    let count = count as usize;
+    let mut records = Vec::with_capacity(count);
+    let mut cursor = BUNDLE_INDEX_HEADER_LEN;
+    for _ in 0..count {
+        let mut hash = [0u64; 2];
+        hash[0] = u64::from_le_bytes(bytes[cursor..cursor + 8].try_into().unwrap());
+        hash[1] = u64::from_le_bytes(bytes[cursor + 8..cursor + 16].try_into().unwrap());
+        let offset = u64::from_le_bytes(bytes[cursor + 16..cursor + 24].try_into().unwrap());
+        let len = u32::from_le_bytes(bytes[cursor + 24..cursor + 28].try_into().unwrap());
+
+        // A zero-length record would underflow the `offset + len - 1`
+        // range-end computation in `load_range_from_source`, so
+        // reject it here rather than trusting it into that
+        // arithmetic.
+        if len == 0 {
+            return Err(fresh_error!("bundle index record has zero length", offset));
         }
+
+        records.push((Fingerprint { hash }, BundleIndexEntry { offset, len }));
+        cursor += BUNDLE_INDEX_RECORD_LEN;
     }
+
+    records.sort_unstable_by_key(|(fprint, _)| fprint.hash);
+    Ok(records)
 }
 
 /// Attempts to load file `name` in `prefix`.
@@ -322,12 +1044,85 @@ fn load_from_cache(prefix: &Path, name: &str) -> Result<Option<Vec<u8>>> {
     }
 }
 
+/// Attempts to load byte range `[offset, offset + len)` of blob
+/// `name` from `source`.
+fn load_range_from_source(
+    source: &Bucket,
+    name: &str,
+    offset: u64,
+    len: u32,
+    cancel: &CancellationToken,
+) -> Result<Option<Vec<u8>>> {
+    use rand::Rng;
+
+    let end = offset + len as u64 - 1;
+    let mut rng = rand::thread_rng();
+    for i in 0..=LOAD_RETRY_LIMIT {
+        if cancel.is_cancelled() {
+            return Ok(None);
+        }
+
+        match source.get_object_range(name, offset, Some(end)) {
+            Ok((payload, 200)) | Ok((payload, 206)) => return Ok(Some(payload)),
+            Ok((_, 404)) => return Ok(None),
+            Ok((body, code)) if code < 500 && code != 429 => {
+                return Err(chain_error!(
+                    (body, code),
+                    "failed to fetch chunk range",
+                    %source.name,
+                    %name,
+                    offset,
+                    len
+                ))
+            }
+            err => {
+                if i == LOAD_RETRY_LIMIT {
+                    return Err(chain_warn!(
+                        err,
+                        "reached load retry limit",
+                        %source.name,
+                        %name,
+                        offset,
+                        len,
+                        LOAD_RETRY_LIMIT
+                    ));
+                }
+
+                let sleep = LOAD_RETRY_BASE_WAIT.mul_f64(LOAD_RETRY_MULTIPLIER.powi(i));
+                let jitter_scale = rng.gen_range(1.0..1.0 + LOAD_RETRY_JITTER_FRAC);
+                let backoff = sleep.mul_f64(jitter_scale);
+
+                tracing::info!(
+                    ?err,
+                    ?backoff,
+                    %source.name,
+                    %name,
+                    offset,
+                    len,
+                    "backing off after a failed ranged GET."
+                );
+                std::thread::sleep(backoff);
+            }
+        }
+    }
+
+    std::unreachable!()
+}
+
 /// Attempts to load the blob `name` from `source`.
-fn load_from_source(source: &Bucket, name: &str) -> Result<Option<Vec<u8>>> {
+fn load_from_source(
+    source: &Bucket,
+    name: &str,
+    cancel: &CancellationToken,
+) -> Result<Option<Vec<u8>>> {
     use rand::Rng;
 
     let mut rng = rand::thread_rng();
     for i in 0..=LOAD_RETRY_LIMIT {
+        if cancel.is_cancelled() {
+            return Ok(None);
+        }
+
         match source.get_object(name) {
             Ok((payload, 200)) => return Ok(Some(payload)),
             Ok((_, 404)) => return Ok(None),
@@ -360,4 +1155,4 @@ fn load_from_source(source: &Bucket, name: &str) -> Result<Option<Vec<u8>>> {
     }
 
     std::unreachable!()
-}
\ No newline at end of file
+}